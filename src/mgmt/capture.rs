@@ -0,0 +1,86 @@
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bytes::*;
+use num_traits::ToPrimitive;
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+
+use crate::mgmt::interface::ManagementRequest;
+
+impl ManagementRequest {
+    /// Encodes this request into the raw frame layout that goes out over the mgmt socket
+    /// (opcode, controller index, param length, params). The client uses it, when capture
+    /// is enabled, to mirror each sent request into a [`BtSnoopWriter`].
+    pub(crate) fn encode(&self) -> Bytes {
+        let mut frame = BytesMut::with_capacity(6 + self.param.len());
+        frame.put_u16_le(self.opcode.to_u16().unwrap());
+        frame.put_u16_le(self.controller.0);
+        frame.put_u16_le(self.param.len() as u16);
+        frame.put_slice(self.param.as_ref());
+        frame.to_bytes()
+    }
+}
+
+/// The literal bytes BTSnoop files are required to start with.
+const BTSNOOP_MAGIC: &[u8; 8] = b"btsnoop\0";
+/// We only ever write the (only) defined BTSnoop format version.
+const BTSNOOP_VERSION: u32 = 1;
+/// Datalink type for the legacy HCI UART (H4) framing. Each record's flags field below is
+/// the direction bit this datalink type defines (bit 0 = received); the Linux Monitor
+/// datalink (2001) instead requires a per-packet monitor opcode pseudo-header identifying
+/// the frame as an Mgmt Command/Event, which we don't emit, so claiming that datalink type
+/// here would make `btmon`/Wireshark misparse every record.
+const BTSNOOP_DATALINK_TYPE_HCI_UART: u32 = 1002;
+/// Microseconds between the BTSnoop epoch (midnight, January 1st, year 0000) and the Unix
+/// epoch, per the BTSnoop file format spec.
+const BTSNOOP_EPOCH_OFFSET_MICROS: u64 = 0x00E0_3AB4_4A67_6000;
+
+/// Writes every mgmt frame sent and received to a BTSnoop file, so the traffic can later be
+/// inspected in Wireshark or `btmon`. See [`crate::mgmt::client::ManagementClient::with_capture`].
+pub struct BtSnoopWriter {
+    file: BufWriter<File>,
+}
+
+impl BtSnoopWriter {
+    pub async fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path).await?);
+
+        file.write_all(BTSNOOP_MAGIC).await?;
+        file.write_u32(BTSNOOP_VERSION).await?;
+        file.write_u32(BTSNOOP_DATALINK_TYPE_HCI_UART).await?;
+        file.flush().await?;
+
+        Ok(BtSnoopWriter { file })
+    }
+
+    pub async fn write_sent(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.write_frame(frame, false).await
+    }
+
+    pub async fn write_received(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.write_frame(frame, true).await
+    }
+
+    async fn write_frame(&mut self, frame: &[u8], received: bool) -> io::Result<()> {
+        let len = frame.len() as u32;
+
+        self.file.write_u32(len).await?; // original length
+        self.file.write_u32(len).await?; // included length, we never truncate
+        self.file.write_u32(received as u32).await?; // flags: bit 0 = received
+        self.file.write_u32(0).await?; // cumulative drops
+        self.file.write_u64(btsnoop_timestamp()).await?;
+        self.file.write_all(frame).await?;
+        self.file.flush().await
+    }
+}
+
+fn btsnoop_timestamp() -> u64 {
+    let unix_micros = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before the Unix epoch")
+        .as_micros() as u64;
+
+    unix_micros + BTSNOOP_EPOCH_OFFSET_MICROS
+}
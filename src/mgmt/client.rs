@@ -1,123 +1,203 @@
-use std::convert::TryInto;
-use std::ffi::OsString;
-use std::os::unix::ffi::OsStringExt;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
 
 use bytes::*;
+use futures::{Stream, StreamExt};
+use tokio::sync::{broadcast, oneshot, Mutex};
+use tokio_stream::wrappers::BroadcastStream;
 
-use crate::Address;
+use crate::mgmt::capture::BtSnoopWriter;
 use crate::mgmt::{ManagementError, Result};
-use crate::mgmt::interface::{
-    ManagementCommand, ManagementCommandStatus, ManagementRequest,
+use crate::mgmt::interface::{ManagementCommandStatus, ManagementRequest, Opcode};
+use crate::mgmt::interface::advertising::{Advertising, AdvertisingFeatures};
+use crate::mgmt::interface::command::ManagementCommand;
+use crate::mgmt::interface::commands::{
+    ReadAdvertisingFeatures, ReadControllerIndexList, ReadControllerInfo, ReadVersionInfo,
+    RemoveAdvertising, SetPowered, StartDiscovery, StartServiceDiscovery, StopDiscovery,
 };
 use crate::mgmt::interface::controller::{Controller, ControllerInfo, ControllerSettings};
+use crate::mgmt::interface::device::AddressTypeFilter;
 use crate::mgmt::interface::event::{ManagementEvent, ManagementVersion};
+use crate::mgmt::interface::response::ManagementResponse;
 use crate::mgmt::socket::ManagementSocket;
 
+/// Number of unsolicited events the broadcast channel keeps for slow subscribers before
+/// it starts dropping them (and reports a `Lagged` error to that subscriber only).
+const EVENTS_CHANNEL_CAPACITY: usize = 64;
+
+type PendingReply = Result<Option<Bytes>>;
+type Waiters = Arc<Mutex<HashMap<(Controller, Opcode), oneshot::Sender<PendingReply>>>>;
+type Capture = Arc<Mutex<BtSnoopWriter>>;
+
 pub struct ManagementClient {
-    socket: ManagementSocket,
+    socket: Arc<ManagementSocket>,
+    waiters: Waiters,
+    events_tx: broadcast::Sender<(Controller, ManagementEvent)>,
+    capture: Option<Capture>,
 }
 
 impl ManagementClient {
     pub fn new() -> Self {
         // todo: fix that unwrap()
+        Self::new_with_capture(None)
+    }
+
+    /// Like [`Self::new`], but also mirrors every sent and received mgmt frame into a
+    /// BTSnoop file at `path`, so the traffic can be inspected later in Wireshark or
+    /// `btmon`.
+    pub async fn with_capture<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let capture = BtSnoopWriter::create(path).await?;
+        Ok(Self::new_with_capture(Some(Arc::new(Mutex::new(capture)))))
+    }
+
+    fn new_with_capture(capture: Option<Capture>) -> Self {
+        let socket = Arc::new(ManagementSocket::open().unwrap());
+        let waiters: Waiters = Arc::new(Mutex::new(HashMap::new()));
+        let (events_tx, _) = broadcast::channel(EVENTS_CHANNEL_CAPACITY);
+
+        tokio::spawn(Self::reader_task(
+            socket.clone(),
+            waiters.clone(),
+            events_tx.clone(),
+            capture.clone(),
+        ));
+
         ManagementClient {
-            socket: ManagementSocket::open().unwrap(),
+            socket,
+            waiters,
+            events_tx,
+            capture,
         }
     }
 
-    #[inline]
-    async fn exec_command<F: FnOnce(Controller, Option<Bytes>) -> Result<T>, T>(
-        &mut self,
-        opcode: ManagementCommand,
-        controller: Controller,
-        param: Option<Bytes>,
-        callback: F,
-    ) -> Result<T> {
-        let param = param.unwrap_or(Bytes::new());
-
-        // send request
-        self.socket
-            .send(ManagementRequest {
-                opcode,
-                controller,
-                param,
-            })
-            .await?;
-
-        // loop until we receive a relevant response
-        // which is either command complete or command status
-        // with the same opcode as the command that we sent
+    /// Runs for the lifetime of the client, demultiplexing every frame the kernel sends
+    /// us: replies matching an in-flight command wake the waiter that registered for
+    /// `(controller, opcode)`, everything else (Index Added, New Settings, Device Found,
+    /// ...) is broadcast to anyone subscribed via [`ManagementClient::events`].
+    ///
+    /// A frame the socket itself couldn't deliver (the kernel side went away) is fatal:
+    /// every waiter is woken with the same error and the task exits. A frame that arrived
+    /// fine but that [`ManagementResponse::parse`] doesn't understand (an event this crate
+    /// doesn't decode yet) is just dropped — it must never be treated the same as a dead
+    /// socket, or the very first such frame would wedge every in-flight and future command.
+    async fn reader_task(
+        socket: Arc<ManagementSocket>,
+        waiters: Waiters,
+        events_tx: broadcast::Sender<(Controller, ManagementEvent)>,
+        capture: Option<Capture>,
+    ) {
         loop {
-            let response = self.socket.receive().await?;
+            let frame = match socket.receive().await {
+                Ok(frame) => frame,
+                Err(err) => {
+                    // the socket is gone for good; wake every pending waiter with the
+                    // same error instead of leaving them parked forever, then stop.
+                    for (_, waiter) in waiters.lock().await.drain() {
+                        let _ = waiter.send(Err(err.clone()));
+                    }
+                    return;
+                }
+            };
+
+            if let Some(capture) = &capture {
+                let _ = capture.lock().await.write_received(&frame).await;
+            }
 
-            // if we got an error, just send that back to the user
-            // otherwise, give the data received to our callback fn
-            match response.event {
+            let response = match ManagementResponse::parse(frame) {
+                Ok(response) => response,
+                Err(_) => continue,
+            };
+
+            let reply = match response.event {
                 ManagementEvent::CommandComplete {
                     status,
                     param,
-                    opcode: evt_opcode,
-                } if opcode == evt_opcode => {
-                    return match status {
-                        ManagementCommandStatus::Success => {
-                            callback(response.controller, Some(param))
-                        }
+                    opcode,
+                } => Some((
+                    opcode,
+                    match status {
+                        ManagementCommandStatus::Success => Ok(Some(param)),
                         _ => Err(ManagementError::CommandError { opcode, status }),
-                    }
-                }
-                ManagementEvent::CommandStatus {
-                    status,
-                    opcode: evt_opcode,
-                } if opcode == evt_opcode => {
-                    return match status {
-                        ManagementCommandStatus::Success => callback(response.controller, None),
+                    },
+                )),
+                ManagementEvent::CommandStatus { status, opcode } => Some((
+                    opcode,
+                    match status {
+                        ManagementCommandStatus::Success => Ok(None),
                         _ => Err(ManagementError::CommandError { opcode, status }),
-                    }
+                    },
+                )),
+                event => {
+                    // no one is required to be listening; a send error just means
+                    // there are currently no subscribers.
+                    let _ = events_tx.send((response.controller, event));
+                    None
+                }
+            };
+
+            if let Some((opcode, reply)) = reply {
+                if let Some(waiter) = waiters.lock().await.remove(&(response.controller, opcode)) {
+                    let _ = waiter.send(reply);
                 }
-                _ => (),
             }
         }
     }
 
+    /// Subscribe to unsolicited management events (Index Added/Removed, New Settings,
+    /// Device Found, ...) across every controller. Each subscriber gets its own queue,
+    /// so a slow consumer only risks lagging itself, not the others.
+    pub fn events(&self) -> impl Stream<Item = (Controller, ManagementEvent)> {
+        BroadcastStream::new(self.events_tx.subscribe()).filter_map(|item| async move { item.ok() })
+    }
+
+    #[inline]
+    async fn exec_command<C: ManagementCommand>(
+        &mut self,
+        controller: Controller,
+        command: C,
+    ) -> Result<C::Reply> {
+        // register a waiter for our reply before sending, so we can't miss it if the
+        // reader task observes the response before we start awaiting the oneshot
+        let (tx, rx) = oneshot::channel();
+        self.waiters
+            .lock()
+            .await
+            .insert((controller, C::OPCODE), tx);
+
+        let request = ManagementRequest {
+            opcode: C::OPCODE,
+            controller,
+            param: command.encode(),
+        };
+
+        if let Some(capture) = &self.capture {
+            let _ = capture.lock().await.write_sent(&request.encode()).await;
+        }
+
+        if let Err(err) = self.socket.send(request).await {
+            self.waiters.lock().await.remove(&(controller, C::OPCODE));
+            return Err(err);
+        }
+
+        let param = rx.await.map_err(|_| ManagementError::Disconnected)??;
+        C::decode(controller, param)
+    }
+
     /// This command returns the Management version and revision.
     //	Besides, being informational the information can be used to
     //	determine whether certain behavior has changed or bugs fixed
     //	when interacting with the kernel.
     pub async fn get_mgmt_version(&mut self) -> Result<ManagementVersion> {
-        self.exec_command(
-            ManagementCommand::ReadVersionInfo,
-            Controller::none(),
-            None,
-            |_, param| {
-                let mut param = param.unwrap();
-                Ok(ManagementVersion {
-                    version: param.get_u8(),
-                    revision: param.get_u16_le(),
-                })
-            },
-        )
-            .await
+        self.exec_command(Controller::none(), ReadVersionInfo).await
     }
 
     /// This command returns the list of currently known controllers.
     //	Controllers added or removed after calling this command can be
     //	monitored using the Index Added and Index Removed events.
     pub async fn get_controller_list(&mut self) -> Result<Vec<Controller>> {
-        self.exec_command(
-            ManagementCommand::ReadControllerIndexList,
-            Controller::none(),
-            None,
-            |_, param| {
-                let mut param = param.unwrap();
-                let count = param.get_u16_le() as usize;
-                let mut controllers = vec![Controller::none(); count];
-                for i in 0..count {
-                    controllers[i] = Controller(param.get_u16_le());
-                }
-
-                Ok(controllers)
-            },
-        )
+        self.exec_command(Controller::none(), ReadControllerIndexList)
             .await
     }
 
@@ -143,26 +223,7 @@ impl ManagementClient {
     //
     //	If no short name is set the Short_Name parameter will be all zeroes.
     pub async fn get_controller_info(&mut self, controller: Controller) -> Result<ControllerInfo> {
-        self.exec_command(
-            ManagementCommand::ReadControllerInfo,
-            controller,
-            None,
-            |_, param| {
-                let mut param = param.unwrap();
-
-                Ok(ControllerInfo {
-                    address: Address::from_slice(param.split_to(6).as_ref()),
-                    bluetooth_version: param.get_u8(),
-                    manufacturer: param.split_to(2).as_ref().try_into().unwrap(),
-                    supported_settings: ControllerSettings::from_bits_truncate(param.get_u32_le()),
-                    current_settings: ControllerSettings::from_bits_truncate(param.get_u32_le()),
-                    class_of_device: param.split_to(3).as_ref().try_into().unwrap(),
-                    name: OsString::from_vec(param.split_to(249).to_vec()),
-                    short_name: OsString::from_vec(param.to_vec()),
-                })
-            },
-        )
-            .await
+        self.exec_command(controller, ReadControllerInfo).await
     }
 
     pub async fn set_powered(
@@ -170,18 +231,85 @@ impl ManagementClient {
         controller: Controller,
         powered: bool,
     ) -> Result<ControllerSettings> {
-        let mut param = BytesMut::with_capacity(1);
-        param.put_u8(powered as u8);
+        self.exec_command(controller, SetPowered { powered }).await
+    }
+
+    /// This command is used to start the discovery of remote devices. A
+    //	Device Found event will be sent for each discovered device.
+    //
+    //	The Address_Type parameter is a bitmask and should specify which
+    //	types of device to discover (BR/EDR, LE Public and/or LE Random).
+    //
+    //	In case a device was found during the discovery, the discovery
+    //	is discovered, but the controller's new settings will not
+    //	reflect this until the Discovering event is received.
+    pub async fn start_discovery(
+        &mut self,
+        controller: Controller,
+        address_type: AddressTypeFilter,
+    ) -> Result<AddressTypeFilter> {
+        self.exec_command(controller, StartDiscovery { address_type })
+            .await
+    }
 
+    /// This command stops the discovery of remote devices started with
+    //	the Start Discovery or Start Service Discovery command.
+    pub async fn stop_discovery(
+        &mut self,
+        controller: Controller,
+        address_type: AddressTypeFilter,
+    ) -> Result<AddressTypeFilter> {
+        self.exec_command(controller, StopDiscovery { address_type })
+            .await
+    }
+
+    /// This command is used to start the discovery of remote devices,
+    //	similarly to the Start Discovery command, but filtering devices
+    //	by the given list of 128-bit service UUIDs and, optionally, an
+    //	RSSI threshold below which devices are not reported.
+    pub async fn start_service_discovery(
+        &mut self,
+        controller: Controller,
+        address_type: AddressTypeFilter,
+        rssi: i8,
+        uuids: Vec<[u8; 16]>,
+    ) -> Result<AddressTypeFilter> {
         self.exec_command(
-            ManagementCommand::SetPowered,
             controller,
-            Some(param.to_bytes()),
-            |_, param| {
-                let mut param = param.unwrap();
-                Ok(ControllerSettings::from_bits_truncate(param.get_u32_le()))
+            StartServiceDiscovery {
+                address_type,
+                rssi,
+                uuids,
             },
         )
             .await
     }
+
+    /// This command returns the advertising features supported by the controller,
+    //	including the maximum advertising/scan response data lengths, the maximum
+    //	number of advertising instances, and the instances currently in use.
+    pub async fn read_advertising_features(
+        &mut self,
+        controller: Controller,
+    ) -> Result<AdvertisingFeatures> {
+        self.exec_command(controller, ReadAdvertisingFeatures)
+            .await
+    }
+
+    /// This command is used to configure and register an advertising instance with the
+    //	kernel. On success, it returns the instance number that is now advertising.
+    pub async fn add_advertising(
+        &mut self,
+        controller: Controller,
+        advertising: Advertising,
+    ) -> Result<u8> {
+        self.exec_command(controller, advertising).await
+    }
+
+    /// This command unregisters an advertising instance, or all of them when `instance`
+    //	is 0.
+    pub async fn remove_advertising(&mut self, controller: Controller, instance: u8) -> Result<u8> {
+        self.exec_command(controller, RemoveAdvertising { instance })
+            .await
+    }
 }
@@ -0,0 +1,110 @@
+use bytes::*;
+
+use crate::mgmt::interface::command::{CheckedBuf, ManagementCommand};
+use crate::mgmt::interface::controller::Controller;
+use crate::mgmt::interface::device::{encode_eir, EirData};
+use crate::mgmt::interface::Opcode;
+use crate::mgmt::{ManagementError, Result};
+
+bitflags! {
+    /// Flags controlling how an advertising instance is broadcast, and which fields the
+    /// kernel should automatically insert into the advertising data for us.
+    pub struct AdvertisingFlags: u32 {
+        const CONNECTABLE = 1 << 0;
+        const GENERAL_DISCOVERABLE = 1 << 1;
+        const LIMITED_DISCOVERABLE = 1 << 2;
+        const MANAGED_FLAGS = 1 << 3;
+        const ADD_TX_POWER = 1 << 4;
+        const ADD_APPEARANCE = 1 << 5;
+        const ADD_LOCAL_NAME = 1 << 6;
+    }
+}
+
+/// Reported by [`crate::mgmt::ManagementClient::read_advertising_features`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdvertisingFeatures {
+    pub supported_flags: AdvertisingFlags,
+    pub max_adv_data_len: u8,
+    pub max_scan_rsp_len: u8,
+    pub max_instances: u8,
+    pub instances: Vec<u8>,
+}
+
+/// Builds the parameters for [`crate::mgmt::ManagementClient::add_advertising`]: an
+/// instance handle plus the advertising/scan response data, built from the same
+/// length/type/value AD-structure encoding used to parse [`crate::mgmt::interface::device::DeviceFound`].
+pub struct Advertising {
+    instance: u8,
+    flags: AdvertisingFlags,
+    duration: u16,
+    timeout: u16,
+    adv_data: BytesMut,
+    scan_rsp: BytesMut,
+}
+
+impl Advertising {
+    pub fn new(instance: u8) -> Self {
+        Advertising {
+            instance,
+            flags: AdvertisingFlags::empty(),
+            duration: 0,
+            timeout: 0,
+            adv_data: BytesMut::new(),
+            scan_rsp: BytesMut::new(),
+        }
+    }
+
+    pub fn flags(mut self, flags: AdvertisingFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// How long, in seconds, this instance is advertised before rotating to the next one.
+    /// `0` leaves it to the kernel's default.
+    pub fn duration(mut self, duration: u16) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// How long, in seconds, this instance stays registered before it is automatically
+    /// removed. `0` means it stays registered until explicitly removed.
+    pub fn timeout(mut self, timeout: u16) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn advertising_data(mut self, data: &[EirData]) -> Self {
+        self.adv_data = encode_eir(data);
+        self
+    }
+
+    pub fn scan_response(mut self, data: &[EirData]) -> Self {
+        self.scan_rsp = encode_eir(data);
+        self
+    }
+}
+
+impl ManagementCommand for Advertising {
+    /// The instance number that is now advertising.
+    type Reply = u8;
+
+    const OPCODE: Opcode = Opcode::AddAdvertising;
+
+    fn encode(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(11 + self.adv_data.len() + self.scan_rsp.len());
+        buf.put_u8(self.instance);
+        buf.put_u32_le(self.flags.bits());
+        buf.put_u16_le(self.duration);
+        buf.put_u16_le(self.timeout);
+        buf.put_u8(self.adv_data.len() as u8);
+        buf.put_u8(self.scan_rsp.len() as u8);
+        buf.put_slice(&self.adv_data);
+        buf.put_slice(&self.scan_rsp);
+        buf.to_bytes()
+    }
+
+    fn decode(_controller: Controller, param: Option<Bytes>) -> Result<Self::Reply> {
+        let mut param = param.ok_or(ManagementError::TooShort)?;
+        param.try_get_u8()
+    }
+}
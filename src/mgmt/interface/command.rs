@@ -0,0 +1,64 @@
+use bytes::*;
+
+use crate::mgmt::interface::controller::Controller;
+use crate::mgmt::interface::Opcode;
+use crate::mgmt::{ManagementError, Result};
+
+/// A single mgmt command as a self-contained typed unit: the value itself is the request
+/// parameters, [`Self::encode`] turns it into the wire param blob, and [`Self::decode`]
+/// turns the matching reply's param blob back into [`Self::Reply`]. Implementors replace
+/// what used to be a one-off closure plus hand-rolled `BytesMut`/`split_to` calls in
+/// `ManagementClient`.
+pub trait ManagementCommand {
+    type Reply;
+
+    /// The opcode this command is sent and replied to under.
+    const OPCODE: Opcode;
+
+    fn encode(&self) -> Bytes;
+
+    fn decode(controller: Controller, param: Option<Bytes>) -> Result<Self::Reply>;
+}
+
+/// Bounds-checked reads over a [`Buf`], so a truncated reply yields
+/// [`ManagementError::TooShort`] instead of panicking the way the raw `bytes` getters do.
+pub(crate) trait CheckedBuf: Buf {
+    fn try_get_u8(&mut self) -> Result<u8> {
+        self.try_require(1)?;
+        Ok(self.get_u8())
+    }
+
+    fn try_get_i8(&mut self) -> Result<i8> {
+        self.try_require(1)?;
+        Ok(self.get_i8())
+    }
+
+    fn try_get_u16_le(&mut self) -> Result<u16> {
+        self.try_require(2)?;
+        Ok(self.get_u16_le())
+    }
+
+    fn try_get_u32_le(&mut self) -> Result<u32> {
+        self.try_require(4)?;
+        Ok(self.get_u32_le())
+    }
+
+    fn try_split_to(&mut self, len: usize) -> Result<Bytes> {
+        self.try_require(len)?;
+        let mut bytes = Vec::with_capacity(len);
+        for _ in 0..len {
+            bytes.push(self.get_u8());
+        }
+        Ok(Bytes::from(bytes))
+    }
+
+    fn try_require(&self, len: usize) -> Result<()> {
+        if self.remaining() < len {
+            Err(ManagementError::TooShort)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<T: Buf> CheckedBuf for T {}
@@ -0,0 +1,260 @@
+use std::convert::TryInto;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStringExt;
+
+use bytes::*;
+
+use crate::mgmt::interface::advertising::{AdvertisingFeatures, AdvertisingFlags};
+use crate::mgmt::interface::command::{CheckedBuf, ManagementCommand};
+use crate::mgmt::interface::controller::{Controller, ControllerInfo, ControllerSettings};
+use crate::mgmt::interface::device::AddressTypeFilter;
+use crate::mgmt::interface::event::ManagementVersion;
+use crate::mgmt::interface::Opcode;
+use crate::mgmt::{ManagementError, Result};
+use crate::Address;
+
+fn require_param(param: Option<Bytes>) -> Result<Bytes> {
+    param.ok_or(ManagementError::TooShort)
+}
+
+/// This command returns the Management version and revision.
+//	Besides, being informational the information can be used to
+//	determine whether certain behavior has changed or bugs fixed
+//	when interacting with the kernel.
+pub struct ReadVersionInfo;
+
+impl ManagementCommand for ReadVersionInfo {
+    type Reply = ManagementVersion;
+
+    const OPCODE: Opcode = Opcode::ReadVersionInfo;
+
+    fn encode(&self) -> Bytes {
+        Bytes::new()
+    }
+
+    fn decode(_controller: Controller, param: Option<Bytes>) -> Result<Self::Reply> {
+        let mut param = require_param(param)?;
+        Ok(ManagementVersion {
+            version: param.try_get_u8()?,
+            revision: param.try_get_u16_le()?,
+        })
+    }
+}
+
+/// This command returns the list of currently known controllers.
+//	Controllers added or removed after calling this command can be
+//	monitored using the Index Added and Index Removed events.
+pub struct ReadControllerIndexList;
+
+impl ManagementCommand for ReadControllerIndexList {
+    type Reply = Vec<Controller>;
+
+    const OPCODE: Opcode = Opcode::ReadControllerIndexList;
+
+    fn encode(&self) -> Bytes {
+        Bytes::new()
+    }
+
+    fn decode(_controller: Controller, param: Option<Bytes>) -> Result<Self::Reply> {
+        let mut param = require_param(param)?;
+        let count = param.try_get_u16_le()? as usize;
+        let mut controllers = Vec::with_capacity(count);
+        for _ in 0..count {
+            controllers.push(Controller(param.try_get_u16_le()?));
+        }
+
+        Ok(controllers)
+    }
+}
+
+/// This command is used to retrieve the current state and basic
+//	information of a controller. It is typically used right after
+//	getting the response to the Read Controller Index List command
+//	or an Index Added event.
+pub struct ReadControllerInfo;
+
+impl ManagementCommand for ReadControllerInfo {
+    type Reply = ControllerInfo;
+
+    const OPCODE: Opcode = Opcode::ReadControllerInfo;
+
+    fn encode(&self) -> Bytes {
+        Bytes::new()
+    }
+
+    fn decode(_controller: Controller, param: Option<Bytes>) -> Result<Self::Reply> {
+        let mut param = require_param(param)?;
+
+        Ok(ControllerInfo {
+            address: Address::from_slice(param.try_split_to(6)?.as_ref()),
+            bluetooth_version: param.try_get_u8()?,
+            manufacturer: param.try_split_to(2)?.as_ref().try_into().unwrap(),
+            supported_settings: ControllerSettings::from_bits_truncate(param.try_get_u32_le()?),
+            current_settings: ControllerSettings::from_bits_truncate(param.try_get_u32_le()?),
+            class_of_device: param.try_split_to(3)?.as_ref().try_into().unwrap(),
+            name: OsString::from_vec(param.try_split_to(249)?.to_vec()),
+            short_name: OsString::from_vec(param.to_vec()),
+        })
+    }
+}
+
+pub struct SetPowered {
+    pub powered: bool,
+}
+
+impl ManagementCommand for SetPowered {
+    type Reply = ControllerSettings;
+
+    const OPCODE: Opcode = Opcode::SetPowered;
+
+    fn encode(&self) -> Bytes {
+        let mut param = BytesMut::with_capacity(1);
+        param.put_u8(self.powered as u8);
+        param.to_bytes()
+    }
+
+    fn decode(_controller: Controller, param: Option<Bytes>) -> Result<Self::Reply> {
+        let mut param = require_param(param)?;
+        Ok(ControllerSettings::from_bits_truncate(
+            param.try_get_u32_le()?,
+        ))
+    }
+}
+
+/// This command is used to start the discovery of remote devices. A
+//	Device Found event will be sent for each discovered device.
+pub struct StartDiscovery {
+    pub address_type: AddressTypeFilter,
+}
+
+impl ManagementCommand for StartDiscovery {
+    type Reply = AddressTypeFilter;
+
+    const OPCODE: Opcode = Opcode::StartDiscovery;
+
+    fn encode(&self) -> Bytes {
+        let mut param = BytesMut::with_capacity(1);
+        param.put_u8(self.address_type.bits());
+        param.to_bytes()
+    }
+
+    fn decode(_controller: Controller, param: Option<Bytes>) -> Result<Self::Reply> {
+        let mut param = require_param(param)?;
+        Ok(AddressTypeFilter::from_bits_truncate(param.try_get_u8()?))
+    }
+}
+
+/// This command stops the discovery of remote devices started with
+//	the Start Discovery or Start Service Discovery command.
+pub struct StopDiscovery {
+    pub address_type: AddressTypeFilter,
+}
+
+impl ManagementCommand for StopDiscovery {
+    type Reply = AddressTypeFilter;
+
+    const OPCODE: Opcode = Opcode::StopDiscovery;
+
+    fn encode(&self) -> Bytes {
+        let mut param = BytesMut::with_capacity(1);
+        param.put_u8(self.address_type.bits());
+        param.to_bytes()
+    }
+
+    fn decode(_controller: Controller, param: Option<Bytes>) -> Result<Self::Reply> {
+        let mut param = require_param(param)?;
+        Ok(AddressTypeFilter::from_bits_truncate(param.try_get_u8()?))
+    }
+}
+
+/// This command is used to start the discovery of remote devices,
+//	similarly to the Start Discovery command, but filtering devices
+//	by the given list of 128-bit service UUIDs and, optionally, an
+//	RSSI threshold below which devices are not reported.
+pub struct StartServiceDiscovery {
+    pub address_type: AddressTypeFilter,
+    pub rssi: i8,
+    pub uuids: Vec<[u8; 16]>,
+}
+
+impl ManagementCommand for StartServiceDiscovery {
+    type Reply = AddressTypeFilter;
+
+    const OPCODE: Opcode = Opcode::StartServiceDiscovery;
+
+    fn encode(&self) -> Bytes {
+        let mut param = BytesMut::with_capacity(4 + self.uuids.len() * 16);
+        param.put_u8(self.address_type.bits());
+        param.put_i8(self.rssi);
+        param.put_u16_le(self.uuids.len() as u16);
+        for uuid in &self.uuids {
+            param.put_slice(uuid);
+        }
+        param.to_bytes()
+    }
+
+    fn decode(_controller: Controller, param: Option<Bytes>) -> Result<Self::Reply> {
+        let mut param = require_param(param)?;
+        Ok(AddressTypeFilter::from_bits_truncate(param.try_get_u8()?))
+    }
+}
+
+/// This command returns the advertising features supported by the controller,
+//	including the maximum advertising/scan response data lengths, the maximum
+//	number of advertising instances, and the instances currently in use.
+pub struct ReadAdvertisingFeatures;
+
+impl ManagementCommand for ReadAdvertisingFeatures {
+    type Reply = AdvertisingFeatures;
+
+    const OPCODE: Opcode = Opcode::ReadAdvertisingFeatures;
+
+    fn encode(&self) -> Bytes {
+        Bytes::new()
+    }
+
+    fn decode(_controller: Controller, param: Option<Bytes>) -> Result<Self::Reply> {
+        let mut param = require_param(param)?;
+
+        let supported_flags = AdvertisingFlags::from_bits_truncate(param.try_get_u32_le()?);
+        let max_adv_data_len = param.try_get_u8()?;
+        let max_scan_rsp_len = param.try_get_u8()?;
+        let max_instances = param.try_get_u8()?;
+        let count = param.try_get_u8()? as usize;
+        let mut instances = Vec::with_capacity(count);
+        for _ in 0..count {
+            instances.push(param.try_get_u8()?);
+        }
+
+        Ok(AdvertisingFeatures {
+            supported_flags,
+            max_adv_data_len,
+            max_scan_rsp_len,
+            max_instances,
+            instances,
+        })
+    }
+}
+
+/// This command unregisters an advertising instance, or all of them when `instance`
+//	is 0.
+pub struct RemoveAdvertising {
+    pub instance: u8,
+}
+
+impl ManagementCommand for RemoveAdvertising {
+    type Reply = u8;
+
+    const OPCODE: Opcode = Opcode::RemoveAdvertising;
+
+    fn encode(&self) -> Bytes {
+        let mut param = BytesMut::with_capacity(1);
+        param.put_u8(self.instance);
+        param.to_bytes()
+    }
+
+    fn decode(_controller: Controller, param: Option<Bytes>) -> Result<Self::Reply> {
+        let mut param = require_param(param)?;
+        param.try_get_u8()
+    }
+}
@@ -0,0 +1,232 @@
+use std::convert::TryInto;
+
+use bytes::*;
+use num_traits::FromPrimitive;
+
+use crate::Address;
+use crate::mgmt::ManagementError;
+
+bitflags! {
+    /// Bitmask of address types to scan for (or that were found), used by the discovery
+    /// commands and reported back on the Discovering event.
+    pub struct AddressTypeFilter: u8 {
+        const BREDR = 0b001;
+        const LE_PUBLIC = 0b010;
+        const LE_RANDOM = 0b100;
+    }
+}
+
+bitflags! {
+    /// Flags reported alongside a Device Found event.
+    pub struct DeviceFoundFlags: u32 {
+        const CONFIRM_NAME = 1 << 0;
+        const LEGACY_PAIRING = 1 << 1;
+        const NOT_CONNECTABLE = 1 << 2;
+    }
+}
+
+/// The address type of a single device, as opposed to [`AddressTypeFilter`] which is a
+/// bitmask of several of these used to configure discovery.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, FromPrimitive)]
+pub enum AddressType {
+    BrEdr = 0x00,
+    LEPublic = 0x01,
+    LERandom = 0x02,
+}
+
+/// A single length/type/value structure out of an Extended Inquiry Response or
+/// Advertising/Scan Response payload.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EirData {
+    Flags(u8),
+    IncompleteServiceUuids16(Vec<u16>),
+    CompleteServiceUuids16(Vec<u16>),
+    IncompleteServiceUuids128(Vec<[u8; 16]>),
+    CompleteServiceUuids128(Vec<[u8; 16]>),
+    ShortenedLocalName(Bytes),
+    CompleteLocalName(Bytes),
+    TxPowerLevel(i8),
+    ManufacturerSpecificData { company_id: u16, data: Bytes },
+    Unknown { ad_type: u8, data: Bytes },
+}
+
+/// Found during device discovery, built from the Device Found (0x0012) event payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceFound {
+    pub address: Address,
+    pub address_type: AddressType,
+    pub rssi: i8,
+    pub flags: DeviceFoundFlags,
+    pub eir_data: Vec<EirData>,
+}
+
+impl DeviceFound {
+    pub(crate) fn parse(buf: &mut Bytes) -> Result<Self, ManagementError> {
+        // addr (6) + addr type (1) + rssi (1) + flags (4) + eir length (2)
+        if buf.remaining() < 14 {
+            return Err(ManagementError::InvalidAdvertisingData);
+        }
+
+        let address = Address::from_slice(buf.split_to(6).as_ref());
+        let address_type = buf.get_u8();
+        let address_type =
+            AddressType::from_u8(address_type).ok_or(ManagementError::InvalidAdvertisingData)?;
+        let rssi = buf.get_u8() as i8;
+        let flags = DeviceFoundFlags::from_bits_truncate(buf.get_u32_le());
+
+        let eir_len = buf.get_u16_le() as usize;
+        if buf.remaining() < eir_len {
+            return Err(ManagementError::InvalidAdvertisingData);
+        }
+        let eir_data = parse_eir(buf.split_to(eir_len))?;
+
+        Ok(DeviceFound {
+            address,
+            address_type,
+            rssi,
+            flags,
+            eir_data,
+        })
+    }
+}
+
+pub(crate) fn encode_eir(entries: &[EirData]) -> BytesMut {
+    let mut buf = BytesMut::new();
+
+    for entry in entries {
+        let (ad_type, value) = match entry {
+            EirData::Flags(flags) => {
+                let mut value = BytesMut::with_capacity(1);
+                value.put_u8(*flags);
+                (0x01, value)
+            }
+            EirData::IncompleteServiceUuids16(uuids) => (0x02, encode_u16_list(uuids)),
+            EirData::CompleteServiceUuids16(uuids) => (0x03, encode_u16_list(uuids)),
+            EirData::IncompleteServiceUuids128(uuids) => (0x06, encode_u128_list(uuids)),
+            EirData::CompleteServiceUuids128(uuids) => (0x07, encode_u128_list(uuids)),
+            EirData::ShortenedLocalName(name) => (0x08, BytesMut::from(name.as_ref())),
+            EirData::CompleteLocalName(name) => (0x09, BytesMut::from(name.as_ref())),
+            EirData::TxPowerLevel(level) => {
+                let mut value = BytesMut::with_capacity(1);
+                value.put_u8(*level as u8);
+                (0x0a, value)
+            }
+            EirData::ManufacturerSpecificData { company_id, data } => {
+                let mut value = BytesMut::with_capacity(2 + data.len());
+                value.put_u16_le(*company_id);
+                value.put_slice(data.as_ref());
+                (0xff, value)
+            }
+            EirData::Unknown { ad_type, data } => (*ad_type, BytesMut::from(data.as_ref())),
+        };
+
+        buf.put_u8(1 + value.len() as u8);
+        buf.put_u8(ad_type);
+        buf.put_slice(&value);
+    }
+
+    buf
+}
+
+fn encode_u16_list(list: &[u16]) -> BytesMut {
+    let mut buf = BytesMut::with_capacity(list.len() * 2);
+    for uuid in list {
+        buf.put_u16_le(*uuid);
+    }
+    buf
+}
+
+fn encode_u128_list(list: &[[u8; 16]]) -> BytesMut {
+    let mut buf = BytesMut::with_capacity(list.len() * 16);
+    for uuid in list {
+        buf.put_slice(uuid);
+    }
+    buf
+}
+
+/// Decodes a sequence of length-prefixed EIR/AD structures (one length byte, one AD-type
+/// byte, `length - 1` bytes of value) into typed [`EirData`] entries. Stops at the first
+/// zero-length structure, or when the buffer is exhausted. Every read is bounds-checked,
+/// so a malformed or truncated payload yields an error instead of a panic.
+pub(crate) fn parse_eir(mut buf: Bytes) -> Result<Vec<EirData>, ManagementError> {
+    let mut entries = Vec::new();
+
+    while buf.has_remaining() {
+        let len = buf.get_u8() as usize;
+        if len == 0 {
+            break;
+        }
+
+        if buf.remaining() < len {
+            return Err(ManagementError::InvalidAdvertisingData);
+        }
+
+        let ad_type = buf.get_u8();
+        let mut value = buf.split_to(len - 1);
+
+        entries.push(match ad_type {
+            0x01 => {
+                if value.remaining() < 1 {
+                    return Err(ManagementError::InvalidAdvertisingData);
+                }
+
+                EirData::Flags(value.get_u8())
+            }
+            0x02 => EirData::IncompleteServiceUuids16(read_u16_list(&mut value)?),
+            0x03 => EirData::CompleteServiceUuids16(read_u16_list(&mut value)?),
+            0x06 => EirData::IncompleteServiceUuids128(read_u128_list(&mut value)?),
+            0x07 => EirData::CompleteServiceUuids128(read_u128_list(&mut value)?),
+            0x08 => EirData::ShortenedLocalName(value),
+            0x09 => EirData::CompleteLocalName(value),
+            0x0a => {
+                if value.remaining() < 1 {
+                    return Err(ManagementError::InvalidAdvertisingData);
+                }
+
+                EirData::TxPowerLevel(value.get_u8() as i8)
+            }
+            0xff => {
+                if value.remaining() < 2 {
+                    return Err(ManagementError::InvalidAdvertisingData);
+                }
+
+                EirData::ManufacturerSpecificData {
+                    company_id: value.get_u16_le(),
+                    data: value,
+                }
+            }
+            ad_type => EirData::Unknown {
+                ad_type,
+                data: value,
+            },
+        });
+    }
+
+    Ok(entries)
+}
+
+fn read_u16_list(buf: &mut Bytes) -> Result<Vec<u16>, ManagementError> {
+    if buf.remaining() % 2 != 0 {
+        return Err(ManagementError::InvalidAdvertisingData);
+    }
+
+    let mut list = Vec::with_capacity(buf.remaining() / 2);
+    while buf.has_remaining() {
+        list.push(buf.get_u16_le());
+    }
+
+    Ok(list)
+}
+
+fn read_u128_list(buf: &mut Bytes) -> Result<Vec<[u8; 16]>, ManagementError> {
+    if buf.remaining() % 16 != 0 {
+        return Err(ManagementError::InvalidAdvertisingData);
+    }
+
+    let mut list = Vec::with_capacity(buf.remaining() / 16);
+    while buf.has_remaining() {
+        list.push(buf.split_to(16).as_ref().try_into().unwrap());
+    }
+
+    Ok(list)
+}
@@ -4,7 +4,9 @@ use std::os::unix::ffi::OsStringExt;
 use bytes::*;
 use num_traits::FromPrimitive;
 
+use crate::mgmt::interface::command::CheckedBuf;
 use crate::mgmt::interface::controller::Controller;
+use crate::mgmt::interface::device::{AddressTypeFilter, DeviceFound};
 use crate::mgmt::interface::event::ManagementEvent;
 use crate::mgmt::ManagementError;
 
@@ -14,20 +16,26 @@ pub struct ManagementResponse {
 }
 
 impl ManagementResponse {
+    /// Parses a single raw mgmt event frame. Every event code this crate doesn't (yet)
+    /// decode -- including ones reserved for future kernel versions -- returns
+    /// [`ManagementError::UnsupportedEvent`] rather than panicking, so an unrecognized
+    /// frame is just an error its caller can choose to ignore. Every read, including the
+    /// header itself, goes through [`CheckedBuf`], so a truncated frame is
+    /// [`ManagementError::TooShort`] instead of a panic.
     pub fn parse<T: Buf>(mut buf: T) -> Result<Self, ManagementError> {
-        let evt_code = buf.get_u16_le();
-        let controller = Controller(buf.get_u16_le());
-        buf.advance(2); // we already know param length
+        let evt_code = buf.try_get_u16_le()?;
+        let controller = Controller(buf.try_get_u16_le()?);
+        buf.try_get_u16_le()?; // we already know param length
 
         Ok(ManagementResponse {
             controller,
             event: match evt_code {
                 0x0001 | 0x0002 => {
-                    let opcode = buf.get_u16_le();
+                    let opcode = buf.try_get_u16_le()?;
                     let opcode = FromPrimitive::from_u16(opcode)
                         .ok_or(ManagementError::UnknownOpcode { opcode })?;
 
-                    let status = buf.get_u8();
+                    let status = buf.try_get_u8()?;
                     let status = FromPrimitive::from_u8(status)
                         .ok_or(ManagementError::UnknownStatus { status })?;
 
@@ -41,19 +49,33 @@ impl ManagementResponse {
                         ManagementEvent::CommandStatus { opcode, status }
                     }
                 }
-                0x0003 => ManagementEvent::ControllerError { code: buf.get_u8() },
+                0x0003 => ManagementEvent::ControllerError {
+                    code: buf.try_get_u8()?,
+                },
                 0x0004 => ManagementEvent::IndexAdded,
                 0x0005 => ManagementEvent::IndexRemoved,
-                0x0006 => unimplemented!("ManagementEvent::NewSettings"),
-                0x0007 => unimplemented!("ManagementEvent::ClassOfDeviceChanged"),
+                0x0006 | 0x0007 => return Err(ManagementError::UnsupportedEvent { code: evt_code }),
                 0x0008 => {
                     let mut buf = buf.to_bytes();
-                    let name = OsString::from_vec(buf.split_to(249).to_vec());
+                    let name = OsString::from_vec(buf.try_split_to(249)?.to_vec());
                     let short_name = OsString::from_vec(buf.to_vec());
 
                     ManagementEvent::LocalNameChanged { name, short_name }
                 }
-                _ => todo!("throw error instead of panicking"),
+                0x0012 => {
+                    let mut buf = buf.to_bytes();
+                    ManagementEvent::DeviceFound(DeviceFound::parse(&mut buf)?)
+                }
+                0x0013 => {
+                    let address_type = AddressTypeFilter::from_bits_truncate(buf.try_get_u8()?);
+                    let discovering = buf.try_get_u8()? != 0;
+
+                    ManagementEvent::Discovering {
+                        address_type,
+                        discovering,
+                    }
+                }
+                _ => return Err(ManagementError::UnsupportedEvent { code: evt_code }),
             },
         })
     }